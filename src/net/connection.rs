@@ -13,19 +13,57 @@
 // License for the specific language governing permissions and limitations under
 // the License.
 
+use rustls::pki_types::ServerName;
 use rustls::{ClientConnection, StreamOwned};
-use std::convert::TryInto;
 use std::io::prelude::*;
 use std::io::Write;
 use std::net::{Shutdown, TcpStream, ToSocketAddrs};
 use std::ops::Add;
 use std::time::{Duration, Instant};
 
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream as AsyncTcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
 use crate::commands::admin_command::AdminCommand;
 use crate::commands::buffer::Buffer;
-use crate::errors::Result;
+use crate::errors::{ErrorKind, Result, ResultExt};
+use crate::net::tls::TlsHandshakeInfo;
 use crate::policy::ClientPolicy;
 
+/// Resolve `addr` and open a TCP connection to the first candidate that
+/// succeeds, bounding each attempt by `timeout` (like deno's `resolve_addr`
+/// followed by `TcpStream::connect_timeout`). A `None` timeout falls back to
+/// the OS default. A timed-out node yields a distinct `Connection` error so
+/// the cluster layer can mark the node down and retry elsewhere.
+fn connect_timeout<T: ToSocketAddrs>(addr: T, timeout: Option<Duration>) -> Result<TcpStream> {
+    let candidates = addr
+        .to_socket_addrs()
+        .chain_err(|| "Failed to resolve socket address")?;
+
+    let mut last_err = None;
+    for socket_addr in candidates {
+        let result = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(&socket_addr, timeout),
+            None => TcpStream::connect(socket_addr),
+        };
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(err) if err.kind() == std::io::ErrorKind::TimedOut => {
+                last_err = Some(ErrorKind::Connection(format!(
+                    "Timed out connecting to {socket_addr}"
+                )));
+            }
+            Err(err) => last_err = Some(ErrorKind::Io(err)),
+        }
+    }
+
+    Err(last_err
+        .unwrap_or_else(|| ErrorKind::Connection("No addresses to connect to".to_string()))
+        .into())
+}
+
 #[derive(Debug)]
 pub struct Connection {
     timeout: Option<Duration>,
@@ -50,13 +88,19 @@ enum Stream {
 
 impl Connection {
     pub fn new<T: ToSocketAddrs + Copy>(addr: T, policy: &ClientPolicy) -> Result<Self> {
-        let tcp_stream = TcpStream::connect(addr)?;
+        let tcp_stream = connect_timeout(addr, policy.connect_timeout)?;
         let stream = match &policy.tls_config {
-            Some(config) => {
-                let server_name = "aerospike.preprod.crto.in".try_into().unwrap();
-                let conn = ClientConnection::new(config.clone(), server_name);
-                let sock = TcpStream::connect(addr)?;
-                let tls = StreamOwned::new(conn.unwrap(), sock);
+            Some(tls) => {
+                // The SNI server name must match the certificate presented by the
+                // node; it comes from the TLS config's `tls_name` rather than a
+                // constant so validation works against real cluster hostnames.
+                // `ServerName` must be owned (`'static`): a borrowed one tied to
+                // `tls.tls_name` can't outlive this match arm, but the
+                // `ClientConnection` built from it has to.
+                let server_name = ServerName::try_from(tls.tls_name.clone())
+                    .chain_err(|| format!("Invalid TLS server name `{}`", tls.tls_name))?;
+                let conn = ClientConnection::new(tls.client_config()?, server_name)?;
+                let tls = StreamOwned::new(conn, tcp_stream);
                 Stream::TLS(tls)
             }
             None => Stream::Plain(tcp_stream),
@@ -150,6 +194,15 @@ impl Connection {
             .map_or(false, |idle_dl| Instant::now() >= idle_dl)
     }
 
+    /// Inspect the parameters negotiated during the TLS handshake. Returns
+    /// `None` for plaintext connections.
+    pub fn tls_handshake_info(&self) -> Option<TlsHandshakeInfo> {
+        match &self.conn {
+            Stream::TLS(s) => Some(TlsHandshakeInfo::from_conn(&s.conn)),
+            Stream::Plain(_) => None,
+        }
+    }
+
     fn refresh(&mut self) {
         self.idle_deadline = None;
         if let Some(idle_to) = self.idle_timeout {
@@ -181,3 +234,222 @@ impl Connection {
         self.bytes_read
     }
 }
+
+/// Async counterpart of [`connect_timeout`]: resolve `addr` and open a Tokio
+/// TCP connection to the first candidate that succeeds, bounding each attempt
+/// with `tokio::time::timeout`. Resolution itself runs through
+/// `tokio::net::lookup_host`, which hands the blocking DNS lookup off to a
+/// runtime blocking thread instead of running it on the calling worker thread.
+async fn async_connect_timeout<T: tokio::net::ToSocketAddrs>(
+    addr: T,
+    timeout: Option<Duration>,
+) -> Result<AsyncTcpStream> {
+    let candidates = tokio::net::lookup_host(addr)
+        .await
+        .chain_err(|| "Failed to resolve socket address")?;
+
+    let mut last_err = None;
+    for socket_addr in candidates {
+        let attempt = AsyncTcpStream::connect(socket_addr);
+        let result = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, attempt).await {
+                Ok(result) => result,
+                Err(_) => {
+                    last_err = Some(ErrorKind::Connection(format!(
+                        "Timed out connecting to {socket_addr}"
+                    )));
+                    continue;
+                }
+            },
+            None => attempt.await,
+        };
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(ErrorKind::Io(err)),
+        }
+    }
+
+    Err(last_err
+        .unwrap_or_else(|| ErrorKind::Connection("No addresses to connect to".to_string()))
+        .into())
+}
+
+/// Bound a pending read/write future by `timeout`, if set, like
+/// [`connect_timeout`]/[`async_connect_timeout`] do for the initial connect.
+/// Plain tokio sockets have no per-op read/write timeout of their own, so
+/// without this a wedged node would hang an `AsyncConnection` call forever.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    fut: impl std::future::Future<Output = std::io::Result<T>>,
+) -> Result<T> {
+    match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, fut).await {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(ErrorKind::Connection("Timed out waiting on node connection".to_string()).into()),
+        },
+        None => Ok(fut.await?),
+    }
+}
+
+#[derive(Debug)]
+enum AsyncStream {
+    TLS(TlsStream<AsyncTcpStream>),
+    Plain(AsyncTcpStream),
+}
+
+/// Async counterpart of [`Connection`] built on `tokio::net::TcpStream` and
+/// `tokio_rustls`. Unlike the blocking variant, `read_buffer`/`write`/`flush`
+/// never park the calling thread, so a Tokio-based connection pool can
+/// multiplex thousands of node sockets without a thread per connection.
+#[derive(Debug)]
+pub struct AsyncConnection {
+    timeout: Option<Duration>,
+
+    // duration after which connection is considered idle
+    idle_timeout: Option<Duration>,
+    idle_deadline: Option<Instant>,
+
+    // connection object
+    conn: AsyncStream,
+
+    bytes_read: usize,
+
+    pub buffer: Buffer,
+}
+
+impl AsyncConnection {
+    pub async fn new<T: tokio::net::ToSocketAddrs + Copy>(addr: T, policy: &ClientPolicy) -> Result<Self> {
+        let sock = async_connect_timeout(addr, policy.connect_timeout).await?;
+        let stream = match &policy.tls_config {
+            Some(tls) => {
+                let server_name = ServerName::try_from(tls.tls_name.clone())
+                    .chain_err(|| format!("Invalid TLS server name `{}`", tls.tls_name))?;
+                let connector = TlsConnector::from(tls.client_config()?);
+                let tls = connector.connect(server_name, sock).await?;
+                AsyncStream::TLS(tls)
+            }
+            None => AsyncStream::Plain(sock),
+        };
+
+        let mut conn = AsyncConnection {
+            buffer: Buffer::new(policy.buffer_reclaim_threshold),
+            bytes_read: 0,
+            timeout: policy.timeout,
+            conn: stream,
+            idle_timeout: policy.idle_timeout,
+            idle_deadline: match policy.idle_timeout {
+                None => None,
+                Some(timeout) => Some(Instant::now() + timeout),
+            },
+        };
+        conn.authenticate(&policy.user_password).await?;
+        conn.refresh();
+        Ok(conn)
+    }
+
+    /// Reject the connection, closing it, if `policy` configures a
+    /// user/password; without credentials this is a no-op.
+    ///
+    /// `AdminCommand::authenticate` is written against the blocking
+    /// `Connection` and has no async counterpart yet, so an authenticated
+    /// cluster cannot be reached over `AsyncConnection` today. Fail loudly
+    /// here rather than silently handing back an unauthenticated connection
+    /// that would only fail once the first command is sent.
+    async fn authenticate(&mut self, user_password: &Option<(String, String)>) -> Result<()> {
+        if user_password.is_some() {
+            self.close().await;
+            bail!(ErrorKind::Connection(
+                "AsyncConnection does not yet support authentication; use the blocking \
+                 Connection for clusters with credentials configured"
+                    .to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        let timeout = self.timeout;
+        match &mut self.conn {
+            AsyncStream::TLS(s) => with_timeout(timeout, s.write_all(&self.buffer.data_buffer)).await?,
+            AsyncStream::Plain(s) => with_timeout(timeout, s.write_all(&self.buffer.data_buffer)).await?,
+        };
+
+        self.refresh();
+        Ok(())
+    }
+
+    pub async fn read_buffer(&mut self, size: usize) -> Result<()> {
+        self.buffer.resize_buffer(size)?;
+        let timeout = self.timeout;
+        match &mut self.conn {
+            AsyncStream::TLS(s) => with_timeout(timeout, s.read_exact(&mut self.buffer.data_buffer)).await?,
+            AsyncStream::Plain(s) => with_timeout(timeout, s.read_exact(&mut self.buffer.data_buffer)).await?,
+        };
+        self.bytes_read += size;
+        self.buffer.reset_offset()?;
+        self.refresh();
+        Ok(())
+    }
+
+    pub async fn write(&mut self, buf: &[u8]) -> Result<()> {
+        let timeout = self.timeout;
+        match &mut self.conn {
+            AsyncStream::TLS(s) => with_timeout(timeout, s.write_all(buf)).await?,
+            AsyncStream::Plain(s) => with_timeout(timeout, s.write_all(buf)).await?,
+        };
+        self.refresh();
+        Ok(())
+    }
+
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<()> {
+        let timeout = self.timeout;
+        match &mut self.conn {
+            AsyncStream::TLS(s) => with_timeout(timeout, s.read_exact(buf)).await?,
+            AsyncStream::Plain(s) => with_timeout(timeout, s.read_exact(buf)).await?,
+        };
+        self.bytes_read += buf.len();
+        self.refresh();
+        Ok(())
+    }
+
+    pub async fn close(&mut self) {
+        match &mut self.conn {
+            AsyncStream::TLS(s) => {
+                let _ = s.get_mut().0.shutdown().await;
+            }
+            AsyncStream::Plain(s) => {
+                let _ = s.shutdown().await;
+            }
+        };
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle_deadline
+            .map_or(false, |idle_dl| Instant::now() >= idle_dl)
+    }
+
+    /// Inspect the parameters negotiated during the TLS handshake. Returns
+    /// `None` for plaintext connections.
+    pub fn tls_handshake_info(&self) -> Option<TlsHandshakeInfo> {
+        match &self.conn {
+            AsyncStream::TLS(s) => Some(TlsHandshakeInfo::from_conn(s.get_ref().1)),
+            AsyncStream::Plain(_) => None,
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.idle_deadline = None;
+        if let Some(idle_to) = self.idle_timeout {
+            self.idle_deadline = Some(Instant::now().add(idle_to))
+        };
+    }
+
+    pub fn bookmark(&mut self) {
+        self.bytes_read = 0;
+    }
+
+    pub const fn bytes_read(&self) -> usize {
+        self.bytes_read
+    }
+}
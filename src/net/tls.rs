@@ -0,0 +1,334 @@
+// Copyright 2015-2018 Aerospike, Inc.
+//
+// Portions may be licensed to Aerospike, Inc. under one or more contributor
+// license agreements.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License. You may obtain a copy of
+// the License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied. See the
+// License for the specific language governing permissions and limitations under
+// the License.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{CipherSuite, ClientConnection, ClientConfig, ProtocolVersion, RootCertStore};
+
+use crate::errors::{ErrorKind, Result, ResultExt};
+
+/// Parameters negotiated during the rustls handshake, analogous to deno's
+/// `TlsHandshakeInfo`. Lets operators record which TLS version, cipher suite,
+/// ALPN protocol and server certificate a node connection is using, and
+/// enables policy checks (e.g. refusing connections below TLS 1.3).
+#[derive(Clone, Debug)]
+pub struct TlsHandshakeInfo {
+    /// Negotiated TLS protocol version, if the handshake has completed.
+    pub protocol_version: Option<ProtocolVersion>,
+    /// Negotiated cipher suite, if the handshake has completed.
+    pub cipher_suite: Option<CipherSuite>,
+    /// Negotiated ALPN protocol, if any was agreed.
+    pub alpn_protocol: Option<Vec<u8>>,
+    /// Certificate chain presented by the peer, leaf first.
+    pub peer_certificates: Option<Vec<CertificateDer<'static>>>,
+}
+
+impl TlsHandshakeInfo {
+    /// Extract the negotiated parameters from a rustls client connection.
+    pub(crate) fn from_conn(conn: &ClientConnection) -> Self {
+        TlsHandshakeInfo {
+            protocol_version: conn.protocol_version(),
+            cipher_suite: conn.negotiated_cipher_suite().map(|suite| suite.suite()),
+            alpn_protocol: conn.alpn_protocol().map(<[u8]>::to_vec),
+            peer_certificates: conn
+                .peer_certificates()
+                .map(|certs| certs.iter().map(|cert| cert.clone().into_owned()).collect()),
+        }
+    }
+}
+
+/// TLS configuration for node connections.
+///
+/// Replaces the previously hardcoded `rustls::ClientConfig`: the SNI
+/// `tls_name` is the hostname passed to [`new`](Self::new), and node
+/// certificates are validated against it. It is not derived from a node's
+/// address or alias, so every connection opened with this configuration uses
+/// the same name. Trusted roots are loaded from PEM bundles, and an optional
+/// client identity enables mutual TLS.
+pub struct TlsConfig {
+    /// Hostname used for `ServerName` validation of the node certificate.
+    pub tls_name: String,
+
+    // Trust anchors used to verify the server certificate.
+    root_store: RootCertStore,
+
+    // Client identity for mutual TLS, if configured.
+    client_auth: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+
+    /// ALPN protocol identifiers to advertise during the handshake, in
+    /// preference order. Used to select a protocol when fronted by an
+    /// ALPN-routing proxy in front of the cluster.
+    pub alpn_protocols: Vec<Vec<u8>>,
+
+    // Opt-in escape hatch that disables certificate verification, gated behind
+    // the `dangerous_configuration` feature so production builds cannot enable
+    // it.
+    #[cfg(feature = "dangerous_configuration")]
+    dangerous: Option<Vec<[u8; 32]>>,
+
+    // Built lazily by `client_config` and reused for every subsequent
+    // `Connection::new`/`AsyncConnection::new` call with this config, so a
+    // pool handing out thousands of connections clones an `Arc` instead of
+    // re-cloning `root_store` and re-running the rustls builder each time.
+    config: OnceLock<Arc<ClientConfig>>,
+}
+
+impl std::fmt::Debug for TlsConfig {
+    // The cached `config` is omitted: `rustls::ClientConfig` doesn't
+    // implement `Debug`, and the fields below already describe it fully.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("TlsConfig");
+        debug
+            .field("tls_name", &self.tls_name)
+            .field("root_store", &self.root_store)
+            .field("client_auth", &self.client_auth.is_some())
+            .field("alpn_protocols", &self.alpn_protocols);
+        #[cfg(feature = "dangerous_configuration")]
+        debug.field("dangerous", &self.dangerous);
+        debug.finish()
+    }
+}
+
+impl Clone for TlsConfig {
+    // The cached `config` is deliberately not carried over: a clone may go on
+    // to change the public `alpn_protocols` field, which (unlike the setters)
+    // does not reset the cache, and a stale cached config would silently
+    // ignore that change.
+    fn clone(&self) -> Self {
+        TlsConfig {
+            tls_name: self.tls_name.clone(),
+            root_store: self.root_store.clone(),
+            client_auth: self.client_auth.clone(),
+            alpn_protocols: self.alpn_protocols.clone(),
+            #[cfg(feature = "dangerous_configuration")]
+            dangerous: self.dangerous.clone(),
+            config: OnceLock::new(),
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Create a configuration that validates node certificates against
+    /// `tls_name` using an empty trust store; add roots with
+    /// [`add_root_certs`](Self::add_root_certs).
+    pub fn new<S: Into<String>>(tls_name: S) -> Self {
+        TlsConfig {
+            tls_name: tls_name.into(),
+            root_store: RootCertStore::empty(),
+            client_auth: None,
+            alpn_protocols: Vec::new(),
+            #[cfg(feature = "dangerous_configuration")]
+            dangerous: None,
+            config: OnceLock::new(),
+        }
+    }
+
+    /// Disable certificate verification, accepting any server certificate.
+    ///
+    /// This is intended for local/dev clusters with self-signed certs and
+    /// mirrors async-rustls / deno's `UnsafelyIgnoreCertificateErrors`. Pass an
+    /// optional allowlist of SHA-256 certificate fingerprints to restrict which
+    /// leaf certificates are accepted; an empty list accepts any certificate.
+    ///
+    /// Gated behind the `dangerous_configuration` feature and default-off so it
+    /// can never be enabled in a production build by accident.
+    #[cfg(feature = "dangerous_configuration")]
+    pub fn set_dangerous_accept_any_cert(&mut self, fingerprints: Vec<[u8; 32]>) -> &mut Self {
+        self.dangerous = Some(fingerprints);
+        self.config = OnceLock::new();
+        self
+    }
+
+    /// Load a PEM root-CA bundle from `path` into the trust store.
+    pub fn add_root_certs<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self> {
+        for cert in load_certs(path.as_ref())? {
+            self.root_store
+                .add(cert)
+                .chain_err(|| "Failed to add root certificate to trust store")?;
+        }
+        self.config = OnceLock::new();
+        Ok(self)
+    }
+
+    /// Enable mutual TLS by presenting the certificate chain and private key
+    /// loaded from the given PEM files.
+    ///
+    /// The chain and key are validated against each other immediately (rather
+    /// than only at connection time) so a mismatched key or unsupported key
+    /// type is reported here, not only as an error the first time a pooled
+    /// connection is opened.
+    pub fn set_client_auth<P: AsRef<Path>>(&mut self, cert: P, key: P) -> Result<&mut Self> {
+        let certs = load_certs(cert.as_ref())?;
+        let key = load_private_key(key.as_ref())?;
+        ClientConfig::builder()
+            .with_root_certificates(RootCertStore::empty())
+            .with_client_auth_cert(certs.clone(), key.clone_key())
+            .chain_err(|| "Client certificate chain and private key do not form a valid keypair")?;
+        self.client_auth = Some((certs, key));
+        self.config = OnceLock::new();
+        Ok(self)
+    }
+
+    /// Return the `rustls::ClientConfig` for this node, building and caching
+    /// it on the first call.
+    pub(crate) fn client_config(&self) -> Result<Arc<ClientConfig>> {
+        if let Some(config) = self.config.get() {
+            return Ok(Arc::clone(config));
+        }
+        let config = Arc::new(self.build_client_config()?);
+        Ok(Arc::clone(self.config.get_or_init(|| config)))
+    }
+
+    fn build_client_config(&self) -> Result<ClientConfig> {
+        let builder = ClientConfig::builder();
+
+        // Both arms land on the same `WantsClientCert` builder state so a
+        // configured `client_auth` (mutual TLS) below still applies even when
+        // `dangerous_configuration` has disabled server certificate
+        // verification.
+        #[cfg(feature = "dangerous_configuration")]
+        let builder = if let Some(fingerprints) = &self.dangerous {
+            let verifier = Arc::new(danger::NoCertificateVerification::new(fingerprints.clone()));
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(verifier)
+        } else {
+            builder.with_root_certificates(self.root_store.clone())
+        };
+
+        #[cfg(not(feature = "dangerous_configuration"))]
+        let builder = builder.with_root_certificates(self.root_store.clone());
+
+        let mut config = match &self.client_auth {
+            Some((certs, key)) => builder
+                .with_client_auth_cert(certs.clone(), key.clone_key())
+                .chain_err(|| "Client certificate chain and private key do not form a valid keypair")?,
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = self.alpn_protocols.clone();
+        Ok(config)
+    }
+}
+
+#[cfg(feature = "dangerous_configuration")]
+mod danger {
+    use ring::digest;
+    use rustls::client::danger::{
+        HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+    };
+    use rustls::crypto::{ring as provider, WebPkiSupportedAlgorithms};
+    use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+    use rustls::{DigitallySignedStruct, Error, SignatureScheme};
+
+    /// A [`ServerCertVerifier`] that accepts any certificate, optionally
+    /// restricted to an allowlist of SHA-256 leaf fingerprints.
+    #[derive(Debug)]
+    pub(super) struct NoCertificateVerification {
+        fingerprints: Vec<[u8; 32]>,
+        supported: WebPkiSupportedAlgorithms,
+    }
+
+    impl NoCertificateVerification {
+        pub(super) fn new(fingerprints: Vec<[u8; 32]>) -> Self {
+            NoCertificateVerification {
+                fingerprints,
+                supported: provider::default_provider().signature_verification_algorithms,
+            }
+        }
+    }
+
+    impl ServerCertVerifier for NoCertificateVerification {
+        fn verify_server_cert(
+            &self,
+            end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, Error> {
+            if self.fingerprints.is_empty() {
+                return Ok(ServerCertVerified::assertion());
+            }
+            let actual = digest::digest(&digest::SHA256, end_entity.as_ref());
+            if self
+                .fingerprints
+                .iter()
+                .any(|fp| fp.as_slice() == actual.as_ref())
+            {
+                Ok(ServerCertVerified::assertion())
+            } else {
+                Err(Error::General(
+                    "Server certificate fingerprint not in allowlist".to_string(),
+                ))
+            }
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, Error> {
+            rustls::crypto::verify_tls12_signature(message, cert, dss, &self.supported)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, Error> {
+            rustls::crypto::verify_tls13_signature(message, cert, dss, &self.supported)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.supported.supported_schemes()
+        }
+    }
+}
+
+/// Load a PEM certificate chain, analogous to deno's `load_certs`.
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(
+        File::open(path).chain_err(|| format!("Failed to open certificate file `{}`", path.display()))?,
+    );
+    let certs = rustls_pemfile::certs(&mut reader)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .chain_err(|| format!("Failed to parse certificates from `{}`", path.display()))?;
+    if certs.is_empty() {
+        bail!(ErrorKind::Connection(format!(
+            "No certificates found in `{}`",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+/// Load the first PEM private key (PKCS#8, RSA or SEC1), analogous to deno's
+/// `load_private_keys` / yodle's `KeyPair`.
+fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(
+        File::open(path).chain_err(|| format!("Failed to open private key file `{}`", path.display()))?,
+    );
+    rustls_pemfile::private_key(&mut reader)
+        .chain_err(|| format!("Failed to parse private key from `{}`", path.display()))?
+        .ok_or_else(|| {
+            ErrorKind::Connection(format!("No private key found in `{}`", path.display())).into()
+        })
+}